@@ -19,6 +19,7 @@ use naming_special_names_rust::classes;
 use naming_special_names_rust::typehints;
 use oxidized::aast_defs::ClassPtrKind;
 use oxidized::aast_defs::Hint;
+use oxidized::aast_defs::HintFun;
 use oxidized::aast_defs::Hint_;
 use oxidized::aast_defs::Hint_::*;
 use oxidized::aast_defs::NastShapeInfo;
@@ -26,6 +27,7 @@ use oxidized::aast_defs::ShapeFieldInfo;
 use oxidized::aast_defs::Tprim;
 use oxidized::aast_defs::TupleInfo;
 use oxidized::ast_defs::Id;
+use oxidized::ast_defs::ParamKind;
 use oxidized::ast_defs::ShapeFieldName;
 
 #[derive(Eq, PartialEq)]
@@ -37,8 +39,12 @@ pub enum Kind {
     UpperBound,
 }
 
+fn is_self_parent_or_static(name: &str) -> bool {
+    name == classes::SELF || name == classes::PARENT || name == classes::STATIC
+}
+
 fn fmt_name_or_prim<'n>(tparams: &[&str], name: &'n str) -> Cow<'n, str> {
-    if tparams.contains(&name) {
+    if tparams.contains(&name) || is_self_parent_or_static(name) {
         name.into()
     } else {
         let id = ClassName::from_ast_name_and_mangle(name);
@@ -90,10 +96,15 @@ pub fn fmt_hint(tparams: &[&str], strip_tparams: bool, hint: &Hint) -> Result<St
         }
         Hwildcard => "_".into(),
         Hfun(hf) => {
-            // TODO(mqian): Implement for inout parameters
+            let readonly_ret = if hf.is_readonly_return.is_some() {
+                "readonly "
+            } else {
+                ""
+            };
             format!(
-                "(function ({}): {})",
-                fmt_hints(tparams, &hf.param_tys)?,
+                "(function ({}): {}{})",
+                fmt_fun_params(tparams, hf)?,
+                readonly_ret,
                 fmt_hint(tparams, false, &hf.return_ty)?
             )
         }
@@ -157,21 +168,46 @@ pub fn fmt_hint(tparams: &[&str], strip_tparams: bool, hint: &Hint) -> Result<St
                 .map(|v| v.join(", "))?;
             string_utils::prefix_namespace("HH", &format!("shape({})", shape_fields))
         }
-        // TODO optional and variadic components T201398626 T201398652
-        Htuple(TupleInfo { required, .. }) => format!("({})", fmt_hints(tparams, required)?),
+        Htuple(TupleInfo {
+            required,
+            optional,
+            variadic,
+        }) => {
+            let mut elts = required
+                .iter()
+                .map(|h| fmt_hint(tparams, false, h))
+                .collect::<Result<Vec<_>>>()?;
+            for h in optional {
+                elts.push(format!("optional {}", fmt_hint(tparams, false, h)?));
+            }
+            if let Some(h) = variadic {
+                elts.push(format!("{}...", fmt_hint(tparams, false, h)?));
+            }
+            format!("({})", elts.join(", "))
+        }
         Hlike(t) => format!("~{}", fmt_hint(tparams, false, t)?),
         Hsoft(t) => format!("@{}", fmt_hint(tparams, false, t)?),
-        HfunContext(_)
-        | Hdynamic
-        | Hintersection(_)
-        | Hmixed
-        | Hnonnull
-        | Hnothing
-        | Hprim(_)
-        | Hthis
-        | Hunion(_)
-        | Hvar(_)
-        | HvecOrDict(_, _) => fmt_name_or_prim(tparams, hint_to_string(h)).into(),
+        Hunion(hints) => hints
+            .iter()
+            .map(|h| fmt_hint(tparams, false, h))
+            .collect::<Result<Vec<_>>>()?
+            .join(" | "),
+        Hintersection(hints) => hints
+            .iter()
+            .map(|h| fmt_hint(tparams, false, h))
+            .collect::<Result<Vec<_>>>()?
+            .join(" & "),
+        HvecOrDict(k, v) => match k {
+            Some(k) => format!(
+                "HH\\vec_or_dict<{}, {}>",
+                fmt_hint(tparams, false, k)?,
+                fmt_hint(tparams, false, v)?
+            ),
+            None => format!("HH\\vec_or_dict<{}>", fmt_hint(tparams, false, v)?),
+        },
+        HfunContext(_) | Hdynamic | Hmixed | Hnonnull | Hnothing | Hprim(_) | Hthis | Hvar(_) => {
+            fmt_name_or_prim(tparams, hint_to_string(h)).into()
+        }
     })
 }
 
@@ -211,6 +247,36 @@ fn fmt_hints(tparams: &[&str], hints: &[Hint]) -> Result<String> {
         .map(|v| v.join(", "))
 }
 
+// Renders inout/readonly param modifiers and the trailing variadic "...".
+fn fmt_fun_params(tparams: &[&str], hf: &HintFun) -> Result<String> {
+    let mut parts = hf
+        .param_tys
+        .iter()
+        .enumerate()
+        .map(|(i, hint)| {
+            let info = hf.param_info.get(i).and_then(|p| p.as_ref());
+            let inout = match info.map(|p| &p.kind) {
+                Some(ParamKind::Pinout(_)) => "inout ",
+                _ => "",
+            };
+            let readonly = match info.and_then(|p| p.readonlyness.as_ref()) {
+                Some(_) => "readonly ",
+                None => "",
+            };
+            Ok(format!(
+                "{}{}{}",
+                inout,
+                readonly,
+                fmt_hint(tparams, false, hint)?
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(variadic) = &hf.variadic_ty {
+        parts.push(format!("{}...", fmt_hint(tparams, false, variadic)?));
+    }
+    Ok(parts.join(", "))
+}
+
 fn can_be_nullable(hint: &Hint_) -> bool {
     match hint {
         Haccess(_, _) | Hfun(_) | Hdynamic | Hnonnull | Hmixed | Hwildcard => false,
@@ -373,6 +439,10 @@ fn type_application_helper(tparams: &[&str], kind: &Kind, name: &str) -> Result<
             name: tc_name,
             flags: TypeConstraintFlags::TypeVar,
         })
+    } else if is_self_parent_or_static(name) {
+        // self/parent/static are late-static-binding pseudo-classes: they must be
+        // emitted verbatim rather than mangled into a concrete class name.
+        Ok(Constraint::intern(name, TypeConstraintFlags::NoFlags))
     } else {
         let name = ClassName::mangle(name);
         Ok(Constraint {
@@ -601,3 +671,193 @@ fn get_flags(tparams: &[&str], flags: TypeConstraintFlags, hint: &Hint_) -> Type
         | Hwildcard => flags,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use oxidized::aast_defs::HfParamInfo;
+    use oxidized::ast_defs::ReadonlyKind;
+    use oxidized::pos::Pos;
+
+    use super::*;
+
+    fn prim_hint(p: Tprim) -> Hint {
+        Hint(Pos::NONE, Box::new(Hprim(p)))
+    }
+
+    fn fun_hint(
+        param_tys: Vec<Hint>,
+        param_info: Vec<Option<HfParamInfo>>,
+        variadic_ty: Option<Hint>,
+        return_ty: Hint,
+        is_readonly_return: Option<ReadonlyKind>,
+    ) -> Hint {
+        Hint(
+            Pos::NONE,
+            Box::new(Hfun(Box::new(HintFun {
+                is_readonly: None,
+                param_tys,
+                param_info,
+                variadic_ty,
+                ctxs: None,
+                return_ty,
+                is_readonly_return,
+            }))),
+        )
+    }
+
+    #[test]
+    fn fun_hint_renders_inout_readonly_variadic_and_readonly_return() {
+        let hint = fun_hint(
+            vec![prim_hint(Tprim::Tint), prim_hint(Tprim::Tstring)],
+            vec![
+                Some(HfParamInfo {
+                    kind: ParamKind::Pinout(Pos::NONE),
+                    readonlyness: None,
+                }),
+                Some(HfParamInfo {
+                    kind: ParamKind::Pnormal,
+                    readonlyness: Some(ReadonlyKind::Readonly),
+                }),
+            ],
+            Some(prim_hint(Tprim::Tint)),
+            prim_hint(Tprim::Tvoid),
+            Some(ReadonlyKind::Readonly),
+        );
+        assert_eq!(
+            fmt_hint(&[], false, &hint).unwrap(),
+            "(function (inout int, readonly string, int...): readonly void)"
+        );
+    }
+
+    fn tuple_hint(required: Vec<Hint>, optional: Vec<Hint>, variadic: Option<Hint>) -> Hint {
+        Hint(
+            Pos::NONE,
+            Box::new(Htuple(TupleInfo {
+                required,
+                optional,
+                variadic,
+            })),
+        )
+    }
+
+    fn happly(name: &str) -> Hint {
+        Hint(
+            Pos::NONE,
+            Box::new(Happly(Id(Pos::NONE, name.to_string()), vec![])),
+        )
+    }
+
+    #[test]
+    fn self_parent_static_pass_through_unmangled() {
+        for name in [classes::SELF, classes::PARENT, classes::STATIC] {
+            assert_eq!(fmt_name_or_prim(&[], name), name);
+        }
+    }
+
+    #[test]
+    fn self_typed_property_constraint_is_unmangled() {
+        let tc =
+            hint_to_type_constraint(&Kind::Property, &[], false, &happly(classes::SELF)).unwrap();
+        assert_eq!(tc.name, Just(hhbc::intern(classes::SELF)));
+        assert_eq!(tc.flags, TypeConstraintFlags::NoFlags);
+    }
+
+    #[test]
+    fn parent_typed_return_constraint_is_unmangled() {
+        let tc =
+            hint_to_type_constraint(&Kind::Return, &[], false, &happly(classes::PARENT)).unwrap();
+        assert_eq!(tc.name, Just(hhbc::intern(classes::PARENT)));
+        assert_eq!(tc.flags, TypeConstraintFlags::NoFlags);
+    }
+
+    #[test]
+    fn static_typed_param_constraint_is_unmangled() {
+        let ti = param_hint_to_type_info(&Kind::Param, false, false, &[], &happly(classes::STATIC))
+            .unwrap();
+        assert_eq!(ti.user_type, Just(hhbc::intern(classes::STATIC)));
+        assert_eq!(ti.type_constraint.name, Just(hhbc::intern(classes::STATIC)));
+        assert_eq!(ti.type_constraint.flags, TypeConstraintFlags::NoFlags);
+    }
+
+    #[test]
+    fn nullable_tuple_can_be_nullable() {
+        let Hint(_, h) = tuple_hint(vec![prim_hint(Tprim::Tint)], vec![], None);
+        assert!(can_be_nullable(&h));
+    }
+
+    #[test]
+    fn tuple_hint_renders_required_optional_and_variadic_members() {
+        let tuple = tuple_hint(
+            vec![prim_hint(Tprim::Tint)],
+            vec![prim_hint(Tprim::Tstring)],
+            Some(prim_hint(Tprim::Tfloat)),
+        );
+        assert_eq!(
+            fmt_hint(&[], false, &tuple).unwrap(),
+            "(int, optional string, float...)"
+        );
+    }
+
+    #[test]
+    fn nullable_tuple_property_gets_nullable_flags() {
+        let tuple = tuple_hint(
+            vec![prim_hint(Tprim::Tint)],
+            vec![prim_hint(Tprim::Tstring)],
+            Some(prim_hint(Tprim::Tfloat)),
+        );
+        let ti = hint_to_type_info(&Kind::Property, false, true, &[], &tuple).unwrap();
+        assert_eq!(
+            ti.type_constraint.flags,
+            TypeConstraintFlags::Nullable | TypeConstraintFlags::DisplayNullable
+        );
+    }
+
+    #[test]
+    fn nullable_tuple_param_gets_nullable_flags() {
+        let tuple = tuple_hint(vec![prim_hint(Tprim::Tint)], vec![], None);
+        let ti = param_hint_to_type_info(&Kind::Param, false, true, &[], &tuple).unwrap();
+        assert_eq!(
+            ti.type_constraint.flags,
+            TypeConstraintFlags::Nullable | TypeConstraintFlags::DisplayNullable
+        );
+    }
+
+    fn union_hint(hints: Vec<Hint>) -> Hint {
+        Hint(Pos::NONE, Box::new(Hunion(hints)))
+    }
+
+    fn intersection_hint(hints: Vec<Hint>) -> Hint {
+        Hint(Pos::NONE, Box::new(Hintersection(hints)))
+    }
+
+    fn vec_or_dict_hint(k: Option<Hint>, v: Hint) -> Hint {
+        Hint(Pos::NONE, Box::new(HvecOrDict(k, v)))
+    }
+
+    #[test]
+    fn union_hint_renders_pipe_joined_members() {
+        let hint = union_hint(vec![prim_hint(Tprim::Tint), prim_hint(Tprim::Tstring)]);
+        assert_eq!(fmt_hint(&[], false, &hint).unwrap(), "int | string");
+    }
+
+    #[test]
+    fn intersection_hint_renders_ampersand_joined_members() {
+        let hint = intersection_hint(vec![prim_hint(Tprim::Tint), prim_hint(Tprim::Tstring)]);
+        assert_eq!(fmt_hint(&[], false, &hint).unwrap(), "int & string");
+    }
+
+    #[test]
+    fn vec_or_dict_hint_renders_keyed_and_unkeyed_forms() {
+        let keyed = vec_or_dict_hint(Some(prim_hint(Tprim::Tarraykey)), prim_hint(Tprim::Tint));
+        assert_eq!(
+            fmt_hint(&[], false, &keyed).unwrap(),
+            "HH\\vec_or_dict<arraykey, int>"
+        );
+
+        let unkeyed = vec_or_dict_hint(None, prim_hint(Tprim::Tint));
+        assert_eq!(
+            fmt_hint(&[], false, &unkeyed).unwrap(),
+            "HH\\vec_or_dict<int>"
+        );
+    }
+}